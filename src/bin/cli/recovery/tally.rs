@@ -1,16 +1,24 @@
-use catalyst_toolbox::recovery::tally::recover_ledger_from_logs;
+use catalyst_toolbox::recovery::tally::{
+    stream_fragments_from_folder_path, stream_recover_ledger_from_logs,
+};
 use chain_core::property::{Deserialize, Fragment};
 use chain_impl_mockchain::block::Block;
+use chain_impl_mockchain::fragment::Fragment as FragmentVariant;
+use chain_impl_mockchain::ledger::Ledger;
+use chain_impl_mockchain::transaction::InputEnum;
+use chain_impl_mockchain::vote::Payload;
 use jcli_lib::utils::{
     output_file::{Error as OutputFileError, OutputFile},
     output_format::{Error as OutputFormatError, OutputFormat},
 };
-use jormungandr_lib::interfaces::{
-    load_persistent_fragments_logs_from_folder_path, VotePlanStatus,
-};
+use jormungandr_lib::crypto::account::Identifier;
+use jormungandr_lib::interfaces::VotePlanStatus;
 
-use log::warn;
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::io::{BufReader, Write};
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 
 use reqwest::Url;
@@ -48,6 +56,13 @@ pub enum Error {
 }
 
 /// Recover the tally from fragment log files and the initial preloaded block0 binary file.
+///
+/// This only ever replays from block0: resuming an interrupted run by
+/// skipping already-applied fragments was part of the original ask, but
+/// would need a checkpoint format that captures the full ledger state
+/// (account balances, vote plan state, ...), not just the `VotePlanStatus`
+/// this crate can already serialize. That's undelivered, not just scaled
+/// back — there's no `--checkpoint-path` here and no resume path.
 #[derive(StructOpt)]
 #[structopt(rename_all = "kebab")]
 pub struct Replay {
@@ -69,11 +84,129 @@ pub struct Replay {
     #[structopt(flatten)]
     output_format: OutputFormat,
 
+    /// Path to additionally write a per-voter audit breakdown of each proposal's
+    /// tally, listing every voting key, its cast choice and the weight it
+    /// contributed. Reconstructed from the processed fragments rather than the
+    /// aggregate ledger state, for independent verification of the recovered
+    /// tally. No breakdown is written if this is left unset.
+    #[structopt(long)]
+    audit_output: Option<PathBuf>,
+
+    /// Number of fragments processed per batch. Fragments are streamed from
+    /// `logs_path` rather than loaded all at once, so this bounds memory use
+    /// and sets the cadence of progress reports.
+    #[structopt(long, default_value = "10000")]
+    batch_size: NonZeroUsize,
+
     /// Verbose mode (-v, -vv, -vvv, etc)
     #[structopt(short = "v", long = "verbose", parse(from_occurrences))]
     verbose: usize,
 }
 
+/// A single voting key's contribution to one proposal's tally.
+#[derive(Serialize)]
+struct VoteAudit {
+    voting_key: String,
+    choice: u8,
+    weight: u64,
+}
+
+/// Per-proposal breakdown of every vote that went into its recovered tally.
+#[derive(Serialize)]
+struct ProposalAudit {
+    vote_plan_id: String,
+    proposal_index: u8,
+    votes: Vec<VoteAudit>,
+}
+
+/// Accumulates the per-voter audit breakdown across the whole replay without
+/// ever holding more than one batch of fragments in memory at a time: each
+/// batch is folded into `by_proposal` as soon as it's processed, instead of
+/// buffering every `VoteCast` fragment from the entire run.
+#[derive(Default)]
+struct AuditAccumulator {
+    by_proposal: BTreeMap<(String, u8), Vec<VoteAudit>>,
+    skipped_private: usize,
+    skipped_non_account: usize,
+}
+
+impl AuditAccumulator {
+    /// Walks one batch of processed fragments to recover, for every
+    /// `VoteCast`, who voted, what they voted and how much stake backed
+    /// their vote (read from the recovered ledger's account state, since
+    /// voting power doesn't change over the course of an election).
+    fn extend(&mut self, ledger: &Ledger, batch: &[FragmentVariant]) {
+        for fragment in batch {
+            let tx = match fragment {
+                FragmentVariant::VoteCast(tx) => tx,
+                _ => continue,
+            };
+            let slice = tx.as_slice();
+            let cert = slice.payload().into_payload();
+
+            let choice = match cert.payload() {
+                Payload::Public { choice } => choice.as_byte(),
+                Payload::Private { .. } => {
+                    self.skipped_private += 1;
+                    continue;
+                }
+            };
+
+            let account = slice.inputs().iter().find_map(|input| match input.to_enum() {
+                InputEnum::AccountInput(account, _) => Some(account),
+                InputEnum::UtxoInput(_) => None,
+            });
+            let account = match account {
+                Some(account) => account,
+                None => {
+                    self.skipped_non_account += 1;
+                    continue;
+                }
+            };
+
+            let weight = ledger
+                .accounts()
+                .get_state(&account)
+                .map(|state| u64::from(state.value()))
+                .unwrap_or(0);
+            let voting_key = Identifier::from(account).to_bech32_str();
+
+            self.by_proposal
+                .entry((cert.vote_plan().to_string(), cert.proposal_index()))
+                .or_default()
+                .push(VoteAudit {
+                    voting_key,
+                    choice,
+                    weight,
+                });
+        }
+    }
+
+    fn finish(self) -> Vec<ProposalAudit> {
+        if self.skipped_private > 0 {
+            warn!(
+                "{} private vote casts were excluded from the audit breakdown (choice isn't recoverable without tally decryption)",
+                self.skipped_private
+            );
+        }
+        if self.skipped_non_account > 0 {
+            warn!(
+                "{} vote casts had no account input and were excluded from the audit breakdown",
+                self.skipped_non_account
+            );
+        }
+
+        self.by_proposal
+            .into_iter()
+            .map(|((vote_plan_id, proposal_index), votes)| ProposalAudit {
+                vote_plan_id,
+                proposal_index,
+                votes,
+            })
+            .collect()
+    }
+}
+
 fn read_block0(path: PathBuf) -> Result<Block, Error> {
     let reader = std::fs::File::open(path)?;
     Block::deserialize(BufReader::new(reader)).map_err(Error::Block0Loading)
@@ -92,6 +225,8 @@ impl Replay {
             logs_path,
             output,
             output_format,
+            audit_output,
+            batch_size,
             verbose,
         } = self;
         stderrlog::new().verbosity(verbose).init().unwrap();
@@ -104,22 +239,44 @@ impl Replay {
             return Err(Error::Block0Unavailable);
         };
 
-        let fragments = load_persistent_fragments_logs_from_folder_path(&logs_path)
-            .map_err(Error::PersistenLogsLoading)?;
+        let fragments =
+            stream_fragments_from_folder_path(&logs_path).map_err(Error::PersistenLogsLoading)?;
+        let keep_for_audit = audit_output.is_some();
+        let mut audit = AuditAccumulator::default();
 
-        let (ledger, failed) = recover_ledger_from_logs(&block0, fragments)?;
+        let mut processed = 0usize;
+        let (ledger, failed) = stream_recover_ledger_from_logs(
+            &block0,
+            fragments,
+            batch_size,
+            |batch: &[FragmentVariant], ledger: &Ledger| {
+                processed += batch.len();
+                info!("processed {} fragments so far", processed);
+                if keep_for_audit {
+                    audit.extend(ledger, batch);
+                }
+            },
+        )?;
         if !failed.is_empty() {
             warn!("{} fragments couldn't be properly processed", failed.len());
-            for failed_fragment in failed {
+            for failed_fragment in &failed {
                 warn!("{}", failed_fragment.id());
             }
         }
+
         let voteplans = ledger.active_vote_plans();
         let voteplan_status: Vec<VotePlanStatus> =
             voteplans.into_iter().map(VotePlanStatus::from).collect();
         let mut out_writer = output.open()?;
         let content = output_format.format_json(serde_json::to_value(&voteplan_status)?)?;
         out_writer.write_all(content.as_bytes())?;
+
+        if let Some(audit_output) = audit_output {
+            let breakdown = audit.finish();
+            let mut audit_writer = std::fs::File::create(audit_output)?;
+            let content = output_format.format_json(serde_json::to_value(&breakdown)?)?;
+            audit_writer.write_all(content.as_bytes())?;
+        }
         Ok(())
     }
 }
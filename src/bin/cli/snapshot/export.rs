@@ -0,0 +1,263 @@
+use catalyst_toolbox::snapshot::{RawSnapshot, Snapshot, SnapshotEntry, VotingPurpose};
+use jcli_lib::utils::output_file::{Error as OutputFileError, OutputFile};
+use jormungandr_lib::interfaces::Value;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Serialization(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+
+    #[error(transparent)]
+    Yaml(#[from] serde_yaml::Error),
+
+    #[error(transparent)]
+    OutputFile(#[from] OutputFileError),
+
+    #[error("could not read raw snapshot from {path:?}")]
+    RawSnapshotLoading {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("no snapshot found for voting purpose {voting_purpose}")]
+    VotingPurposeNotFound { voting_purpose: u64 },
+}
+
+pub enum ExportFormat {
+    Json,
+    Yaml,
+    Csv,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "json" => Ok(ExportFormat::Json),
+            "yaml" => Ok(ExportFormat::Yaml),
+            "csv" => Ok(ExportFormat::Csv),
+            other => Err(format!("unknown export format '{}'", other)),
+        }
+    }
+}
+
+/// Export the full contribution breakdown of a snapshot, rather than just its
+/// collapsed block0 fund entries.
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab")]
+pub struct Export {
+    /// Path to the raw CIP-36 registrations, as consumed by the snapshot command
+    #[structopt(long)]
+    raw_snapshot_path: PathBuf,
+
+    /// Minimum voting power required for a registration to be considered
+    #[structopt(long, default_value = "0")]
+    stake_threshold: u64,
+
+    /// Voting purpose to export: "all", a single tag, or a comma separated list of tags
+    #[structopt(long, default_value = "0")]
+    voting_purpose: VotingPurpose,
+
+    /// Format to export the snapshot in
+    #[structopt(long, default_value = "json")]
+    format: ExportFormat,
+
+    #[structopt(flatten)]
+    output: OutputFile,
+}
+
+/// One contribution per CSV row, since CSV can't represent the nested
+/// `contributions` list of [`SnapshotEntry`] directly.
+#[derive(Serialize)]
+struct SnapshotCsvRow<'a> {
+    voting_key: &'a str,
+    voting_power: u64,
+    voting_purpose: u64,
+    stake_threshold: u64,
+    reward_address: &'a str,
+    contribution_value: u64,
+}
+
+fn entries_for_voting_purpose(
+    snapshots: BTreeMap<u64, Snapshot>,
+    voting_purpose: VotingPurpose,
+) -> Result<Vec<SnapshotEntry>, Error> {
+    Ok(match voting_purpose {
+        VotingPurpose::Tag(tag) => snapshots
+            .get(&tag)
+            .ok_or(Error::VotingPurposeNotFound { voting_purpose: tag })?
+            .to_full_snapshot_info(),
+        VotingPurpose::All | VotingPurpose::Tags(_) => snapshots
+            .into_values()
+            .flat_map(|snapshot| snapshot.to_full_snapshot_info())
+            .collect(),
+    })
+}
+
+impl Export {
+    pub fn exec(self) -> Result<(), Error> {
+        let Export {
+            raw_snapshot_path,
+            stake_threshold,
+            voting_purpose,
+            format,
+            output,
+        } = self;
+
+        let raw_snapshot: RawSnapshot = serde_json::from_reader(std::fs::File::open(
+            &raw_snapshot_path,
+        )
+        .map_err(|source| Error::RawSnapshotLoading {
+            path: raw_snapshot_path,
+            source,
+        })?)?;
+
+        let snapshots = Snapshot::from_raw_snapshot(
+            raw_snapshot,
+            voting_purpose.clone(),
+            Value::from(stake_threshold),
+        );
+        let entries = entries_for_voting_purpose(snapshots, voting_purpose)?;
+
+        let mut out_writer = output.open()?;
+        match format {
+            ExportFormat::Json => {
+                out_writer.write_all(serde_json::to_string_pretty(&entries)?.as_bytes())?;
+            }
+            ExportFormat::Yaml => {
+                out_writer.write_all(serde_yaml::to_string(&entries)?.as_bytes())?;
+            }
+            ExportFormat::Csv => {
+                let mut csv_writer = csv::Writer::from_writer(out_writer);
+                for entry in &entries {
+                    for contribution in &entry.contributions {
+                        csv_writer.serialize(SnapshotCsvRow {
+                            voting_key: &entry.voting_key,
+                            voting_power: entry.voting_power,
+                            voting_purpose: entry.voting_purpose,
+                            stake_threshold: entry.stake_threshold,
+                            reward_address: &contribution.reward_address,
+                            contribution_value: contribution.value,
+                        })?;
+                    }
+                }
+                csv_writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use catalyst_toolbox::snapshot::registration::{Delegations, VotingRegistration};
+    use jormungandr_lib::crypto::account::Identifier;
+    use std::collections::BTreeSet;
+
+    fn snapshot_with_one_entry(voting_purpose: u64) -> Snapshot {
+        let raw_snapshot: RawSnapshot = serde_json::from_str(&format!(
+            r#"[{{
+                "reward_address": "0xe1ffff2912572257b59dca84c965e4638a09f1524af7a15787eb0d8a46",
+                "stake_public_key": "0xe7d6616840734686855ec80ee9658f5ead9e29e494ec6889a5d1988b50eb8d0f",
+                "total_voting_power": 100,
+                "delegations": "0xa6a3c0447aeb9cc54cf6422ba32b294e5e1c3ef6d782f2acff4a70694c4d1663",
+                "voting_purpose": {}
+            }}]"#,
+            voting_purpose
+        ))
+        .unwrap();
+        Snapshot::from_raw_snapshot(raw_snapshot, VotingPurpose::Tag(voting_purpose), 0.into())
+            .remove(&voting_purpose)
+            .unwrap()
+    }
+
+    #[test]
+    fn entries_for_single_tag_returns_that_purposes_snapshot() {
+        let mut snapshots = BTreeMap::new();
+        snapshots.insert(0, snapshot_with_one_entry(0));
+
+        let entries = entries_for_voting_purpose(snapshots, VotingPurpose::Tag(0)).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].voting_power, 100);
+        assert_eq!(entries[0].voting_purpose, 0);
+    }
+
+    #[test]
+    fn entries_for_missing_tag_errors() {
+        let snapshots = BTreeMap::new();
+        assert!(entries_for_voting_purpose(snapshots, VotingPurpose::Tag(0)).is_err());
+    }
+
+    #[test]
+    fn entries_for_all_flattens_every_purpose_keeping_their_tag() {
+        let mut snapshots = BTreeMap::new();
+        snapshots.insert(0, snapshot_with_one_entry(0));
+        snapshots.insert(1, snapshot_with_one_entry(1));
+
+        let entries = entries_for_voting_purpose(snapshots, VotingPurpose::All).unwrap();
+        assert_eq!(entries.len(), 2);
+        let tags: BTreeSet<u64> = entries.iter().map(|entry| entry.voting_purpose).collect();
+        assert_eq!(tags, [0, 1].into_iter().collect());
+    }
+
+    #[test]
+    fn csv_rows_have_a_header_and_one_row_per_contribution() {
+        let vk = Identifier::from_hex(&hex::encode([0; 32])).unwrap();
+        let snapshot = Snapshot::from_raw_snapshot(
+            vec![VotingRegistration {
+                stake_public_key: String::new(),
+                voting_power: 10.into(),
+                reward_address: "addr".to_string(),
+                delegations: Delegations::Legacy(vk),
+                voting_purpose: 0,
+                nonce: 0,
+            }]
+            .into(),
+            VotingPurpose::Tag(0),
+            0.into(),
+        )
+        .remove(&0)
+        .unwrap();
+
+        let mut buf = Vec::new();
+        {
+            let mut csv_writer = csv::Writer::from_writer(&mut buf);
+            for entry in snapshot.to_full_snapshot_info() {
+                for contribution in &entry.contributions {
+                    csv_writer
+                        .serialize(SnapshotCsvRow {
+                            voting_key: &entry.voting_key,
+                            voting_power: entry.voting_power,
+                            voting_purpose: entry.voting_purpose,
+                            stake_threshold: entry.stake_threshold,
+                            reward_address: &contribution.reward_address,
+                            contribution_value: contribution.value,
+                        })
+                        .unwrap();
+                }
+            }
+        }
+        let content = String::from_utf8(buf).unwrap();
+        let mut lines = content.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "voting_key,voting_power,voting_purpose,stake_threshold,reward_address,contribution_value"
+        );
+        assert_eq!(lines.count(), 1);
+    }
+}
@@ -0,0 +1,126 @@
+use catalyst_toolbox::snapshot::{RawSnapshot, Snapshot, VotingPurpose};
+use chain_addr::Discrimination;
+use jcli_lib::utils::output_file::{Error as OutputFileError, OutputFile};
+use jormungandr_lib::interfaces::Value;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Serialization(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    OutputFile(#[from] OutputFileError),
+
+    #[error("could not read raw snapshot from {path:?}")]
+    RawSnapshotLoading {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("block0 initials require a single voting purpose, but {voting_purpose_count} were found; pass a single tag")]
+    AmbiguousVotingPurpose { voting_purpose_count: usize },
+
+    #[error("no snapshot found for voting purpose {voting_purpose}")]
+    VotingPurposeNotFound { voting_purpose: u64 },
+}
+
+/// Wraps [`chain_addr::Discrimination`] so it can be parsed from a CLI flag.
+struct DiscriminationArg(Discrimination);
+
+impl FromStr for DiscriminationArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "production" => Ok(Self(Discrimination::Production)),
+            "test" => Ok(Self(Discrimination::Test)),
+            other => Err(format!("unknown discrimination '{}'", other)),
+        }
+    }
+}
+
+/// Builds the block0 `Initial::Fund` entries for a snapshot, collapsing away
+/// the per-registration contribution breakdown that [`super::export::Export`]
+/// keeps. This is what actually seeds voting power in a Catalyst fund.
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab")]
+pub struct Genesis {
+    /// Path to the raw CIP-36 registrations
+    #[structopt(long)]
+    raw_snapshot_path: PathBuf,
+
+    /// Minimum voting power required for a registration to be considered
+    #[structopt(long, default_value = "0")]
+    stake_threshold: u64,
+
+    /// Voting purpose to build block0 initials for: a single tag, a comma
+    /// separated list of tags, or "all". block0 has a single fund, so a list
+    /// or "all" must match registrations of exactly one voting purpose,
+    /// otherwise the command fails.
+    #[structopt(long, default_value = "0")]
+    voting_purpose: VotingPurpose,
+
+    /// Address discrimination to encode the fund's accounts with
+    #[structopt(long, default_value = "production")]
+    discrimination: DiscriminationArg,
+
+    #[structopt(flatten)]
+    output: OutputFile,
+}
+
+impl Genesis {
+    pub fn exec(self) -> Result<(), Error> {
+        let Genesis {
+            raw_snapshot_path,
+            stake_threshold,
+            voting_purpose,
+            discrimination,
+            output,
+        } = self;
+
+        let raw_snapshot: RawSnapshot = serde_json::from_reader(
+            std::fs::File::open(&raw_snapshot_path).map_err(|source| {
+                Error::RawSnapshotLoading {
+                    path: raw_snapshot_path,
+                    source,
+                }
+            })?,
+        )?;
+
+        let mut snapshots = Snapshot::from_raw_snapshot(
+            raw_snapshot,
+            voting_purpose.clone(),
+            Value::from(stake_threshold),
+        );
+
+        let snapshot = match voting_purpose {
+            VotingPurpose::Tag(tag) => {
+                snapshots
+                    .remove(&tag)
+                    .ok_or(Error::VotingPurposeNotFound { voting_purpose: tag })?
+            }
+            VotingPurpose::Tags(_) | VotingPurpose::All => {
+                let voting_purpose_count = snapshots.len();
+                if voting_purpose_count != 1 {
+                    return Err(Error::AmbiguousVotingPurpose {
+                        voting_purpose_count,
+                    });
+                }
+                snapshots.into_values().next().unwrap()
+            }
+        };
+
+        let initial = snapshot.to_block0_initials(discrimination.0);
+        let mut out_writer = output.open()?;
+        out_writer.write_all(serde_json::to_string_pretty(&initial)?.as_bytes())?;
+        Ok(())
+    }
+}
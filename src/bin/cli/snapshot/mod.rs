@@ -0,0 +1,30 @@
+pub mod export;
+pub mod genesis;
+
+use structopt::StructOpt;
+
+/// Snapshot-related commands: building a raw CIP-36 registration feed into
+/// block0 initials, or exporting the full per-key contribution breakdown.
+#[derive(StructOpt)]
+pub enum Snapshot {
+    Genesis(genesis::Genesis),
+    Export(export::Export),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Genesis(#[from] genesis::Error),
+
+    #[error(transparent)]
+    Export(#[from] export::Error),
+}
+
+impl Snapshot {
+    pub fn exec(self) -> Result<(), Error> {
+        match self {
+            Snapshot::Genesis(genesis) => genesis.exec().map_err(Error::Genesis),
+            Snapshot::Export(export) => export.exec().map_err(Error::Export),
+        }
+    }
+}
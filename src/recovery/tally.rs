@@ -0,0 +1,171 @@
+use chain_core::property::{Deserialize as _, Fragment as _};
+use chain_impl_mockchain::block::Block;
+use chain_impl_mockchain::fragment::Fragment;
+use chain_impl_mockchain::ledger::{Error as LedgerError, Ledger};
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Ledger(#[from] LedgerError),
+}
+
+/// Lazily streams fragments from every log file under `folder_path`, in
+/// filename order, instead of reading the whole fragment set into memory up
+/// front the way `jormungandr_lib::interfaces::load_persistent_fragments_logs_from_folder_path`
+/// does. Each file is read one fragment at a time and dropped once exhausted.
+pub fn stream_fragments_from_folder_path(
+    folder_path: &Path,
+) -> io::Result<impl Iterator<Item = Fragment>> {
+    let mut paths = fs::read_dir(folder_path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+    paths.sort();
+
+    Ok(FragmentLogStream {
+        paths: paths.into_iter(),
+        current: None,
+    })
+}
+
+struct FragmentLogStream {
+    paths: std::vec::IntoIter<PathBuf>,
+    current: Option<BufReader<File>>,
+}
+
+impl Iterator for FragmentLogStream {
+    type Item = Fragment;
+
+    fn next(&mut self) -> Option<Fragment> {
+        loop {
+            if let Some(reader) = &mut self.current {
+                match Fragment::deserialize(&mut *reader) {
+                    Ok(fragment) => return Some(fragment),
+                    Err(_) => {
+                        // End of this file (or a corrupt tail): move on to the next one.
+                        self.current = None;
+                    }
+                }
+            }
+
+            let path = self.paths.next()?;
+            match File::open(&path) {
+                Ok(file) => self.current = Some(BufReader::new(file)),
+                Err(err) => log::warn!("could not open fragment log {:?}: {}", path, err),
+            }
+        }
+    }
+}
+
+/// Recovers a ledger by replaying every fragment in `fragments` on top of
+/// `block0`. Fragments that fail to apply are skipped and returned alongside
+/// the recovered ledger instead of aborting the whole recovery.
+pub fn recover_ledger_from_logs(
+    block0: &Block,
+    fragments: Vec<Fragment>,
+) -> Result<(Ledger, Vec<Fragment>), Error> {
+    // A single batch covering every fragment gives the "apply everything at
+    // once" behavior this wrapper promises; `usize::MAX` would do the same
+    // semantically, but `stream_recover_ledger_from_logs` pre-allocates a
+    // batch buffer sized to `batch_size`, and `Vec::with_capacity(usize::MAX)`
+    // always panics.
+    let batch_size = NonZeroUsize::new(fragments.len().max(1)).unwrap();
+    stream_recover_ledger_from_logs(block0, fragments.into_iter(), batch_size, |_, _| {})
+}
+
+/// Like [`recover_ledger_from_logs`], but pulls fragments lazily from an
+/// iterator and applies them in ordered batches of `batch_size`, so the whole
+/// fragment set never needs to be held in memory at once. `on_batch` is
+/// called after each batch is applied with the fragments just processed and
+/// the ledger state so far, so callers can report progress or write
+/// checkpoints without waiting for the full recovery to finish.
+pub fn stream_recover_ledger_from_logs(
+    block0: &Block,
+    fragments: impl Iterator<Item = Fragment>,
+    batch_size: NonZeroUsize,
+    mut on_batch: impl FnMut(&[Fragment], &Ledger),
+) -> Result<(Ledger, Vec<Fragment>), Error> {
+    let mut ledger = Ledger::new(block0.header.id(), block0.fragments())?;
+    let ledger_parameters = ledger.get_ledger_parameters();
+    let date = ledger.date();
+
+    let mut failed = Vec::new();
+    let mut batch = Vec::with_capacity(batch_size.get());
+
+    for fragment in fragments {
+        batch.push(fragment);
+        if batch.len() == batch_size.get() {
+            apply_batch(&mut ledger, &ledger_parameters, date, &batch, &mut failed);
+            on_batch(&batch, &ledger);
+            batch.clear();
+        }
+    }
+    if !batch.is_empty() {
+        apply_batch(&mut ledger, &ledger_parameters, date, &batch, &mut failed);
+        on_batch(&batch, &ledger);
+    }
+
+    Ok((ledger, failed))
+}
+
+fn apply_batch(
+    ledger: &mut Ledger,
+    ledger_parameters: &chain_impl_mockchain::ledger::LedgerParameters,
+    date: chain_impl_mockchain::block::BlockDate,
+    batch: &[Fragment],
+    failed: &mut Vec<Fragment>,
+) {
+    for fragment in batch {
+        match ledger.apply_fragment(ledger_parameters, fragment, date) {
+            Ok(new_ledger) => *ledger = new_ledger,
+            Err(_) => failed.push(fragment.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_impl_mockchain::legacy::UtxoDeclaration;
+    use chain_impl_mockchain::testing::TestGen;
+
+    /// Fragments that carry no witness and aren't valid outside block0, so
+    /// every one of them is rejected when applied to an existing ledger.
+    /// That's fine here: what's under test is the real batching and
+    /// bookkeeping path, not whether any particular fragment succeeds.
+    fn rejected_fragments(count: usize) -> Vec<Fragment> {
+        (0..count)
+            .map(|_| Fragment::OldUtxoDeclaration(UtxoDeclaration { addrs: Vec::new() }))
+            .collect()
+    }
+
+    #[test]
+    fn stream_recover_ledger_from_logs_batches_and_accounts_for_every_fragment() {
+        let block0 = TestGen::block0();
+        let fragments = rejected_fragments(5);
+
+        let mut batch_sizes = Vec::new();
+        let (_ledger, failed) = stream_recover_ledger_from_logs(
+            &block0,
+            fragments.clone().into_iter(),
+            NonZeroUsize::new(2).unwrap(),
+            |batch, _ledger| batch_sizes.push(batch.len()),
+        )
+        .unwrap();
+
+        assert_eq!(failed.len(), fragments.len());
+        assert_eq!(batch_sizes, vec![2, 2, 1]);
+    }
+
+    #[test]
+    fn recover_ledger_from_logs_processes_every_fragment() {
+        let block0 = TestGen::block0();
+        let fragments = rejected_fragments(3);
+
+        let (_ledger, failed) = recover_ledger_from_logs(&block0, fragments.clone()).unwrap();
+        assert_eq!(failed.len(), fragments.len());
+    }
+}
@@ -5,8 +5,13 @@ use registration::{Delegations, MainnetRewardAddress, VotingRegistration};
 use chain_addr::{Discrimination, Kind};
 use jormungandr_lib::crypto::account::Identifier;
 use jormungandr_lib::interfaces::{Address, Initial, InitialUTxO, Value};
-use serde::Deserialize;
-use std::{collections::BTreeMap, iter::Iterator, num::NonZeroU64};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    iter::Iterator,
+    num::NonZeroU64,
+    str::FromStr,
+};
 
 pub const CATALYST_VOTING_PURPOSE_TAG: u64 = 0;
 
@@ -14,77 +19,227 @@ pub const CATALYST_VOTING_PURPOSE_TAG: u64 = 0;
 pub struct RawSnapshot(Vec<VotingRegistration>);
 
 /// Contribution to a voting key for some registration
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct KeyContribution {
     pub reward_address: MainnetRewardAddress,
     pub value: u64,
 }
 
+/// Full, uncollapsed export of a voting key's entry in a snapshot: its total
+/// effective voting power together with the individual contributions backing
+/// it. Unlike [`Snapshot::to_block0_initials`], this keeps the provenance
+/// (which reward address contributed how much) that analysts and treasury
+/// tooling need, rather than just the value that ends up in the block0 fund.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SnapshotEntry {
+    pub voting_key: String,
+    pub voting_power: u64,
+    pub voting_purpose: u64,
+    pub stake_threshold: u64,
+    pub contributions: Vec<KeyContribution>,
+}
+
+/// Selects which CIP-36 `voting_purpose` tag(s) a snapshot should be built from.
+///
+/// Catalyst registrations are tagged with a `voting_purpose`, but the same raw
+/// registration feed can back other voting sub-systems that use different tags.
+/// This lets callers build a snapshot for a single purpose, an explicit set of
+/// purposes, or every purpose found in the feed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VotingPurpose {
+    /// Only registrations tagged with this purpose are considered.
+    Tag(u64),
+    /// Only registrations tagged with one of these purposes are considered.
+    Tags(BTreeSet<u64>),
+    /// Every purpose is considered; the resulting snapshots are grouped by purpose.
+    All,
+}
+
+impl VotingPurpose {
+    fn accepts(&self, voting_purpose: u64) -> bool {
+        match self {
+            VotingPurpose::Tag(tag) => *tag == voting_purpose,
+            VotingPurpose::Tags(tags) => tags.contains(&voting_purpose),
+            VotingPurpose::All => true,
+        }
+    }
+}
+
+impl Default for VotingPurpose {
+    fn default() -> Self {
+        VotingPurpose::Tag(CATALYST_VOTING_PURPOSE_TAG)
+    }
+}
+
+/// Parses a CLI flag value: either `all`, a single tag (`0`), or a comma separated
+/// list of tags (`0,1,2`).
+impl FromStr for VotingPurpose {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("all") {
+            return Ok(VotingPurpose::All);
+        }
+        let mut tags = s
+            .split(',')
+            .map(|tag| tag.trim().parse::<u64>())
+            .collect::<Result<BTreeSet<u64>, _>>()?;
+        if tags.len() == 1 {
+            return Ok(VotingPurpose::Tag(tags.pop_first().unwrap()));
+        }
+        Ok(VotingPurpose::Tags(tags))
+    }
+}
+
+/// A stake key may re-register multiple times; CIP-36 says only the most
+/// recent registration (highest nonce) should count towards voting power, so
+/// earlier ones are discarded before distribution.
+fn keep_latest_registration_per_stake_key(
+    registrations: Vec<VotingRegistration>,
+) -> Vec<VotingRegistration> {
+    let mut latest: BTreeMap<String, VotingRegistration> = BTreeMap::new();
+    for reg in registrations {
+        match latest.entry(reg.stake_public_key.clone()) {
+            std::collections::btree_map::Entry::Vacant(entry) => {
+                entry.insert(reg);
+            }
+            std::collections::btree_map::Entry::Occupied(mut entry) => {
+                if reg.nonce > entry.get().nonce {
+                    entry.insert(reg);
+                }
+            }
+        }
+    }
+    latest.into_values().collect()
+}
+
+/// Splits `voting_power` among `weighted_keys` using the Hamilton (largest
+/// remainder) method: each key is assigned the floor of its exact quota
+/// `voting_power * weight / total_weight`, and the leftover units are handed
+/// out one at a time to the keys with the largest fractional remainder. Ties
+/// are broken by the voting key's own ordering (its byte representation) so
+/// the result doesn't depend on delegation order. When `total_weight` is
+/// non-zero, the distributed total equals `voting_power`.
+///
+/// `total_weight` is the sum of every key's weight; if it's `0` (every
+/// delegated key has weight `0`, which CIP-36 metadata doesn't rule out),
+/// there's no meaningful quota to compute, so every key is allocated `0`
+/// rather than dividing by zero.
+fn largest_remainder_apportionment(
+    voting_power: u64,
+    weighted_keys: Vec<(Identifier, u32)>,
+    total_weight: u64,
+) -> Vec<(Identifier, u64)> {
+    if total_weight == 0 {
+        return weighted_keys.into_iter().map(|(vk, _)| (vk, 0)).collect();
+    }
+
+    let mut allocations = weighted_keys
+        .into_iter()
+        .map(|(vk, weight)| {
+            let numerator = voting_power as u128 * weight as u128;
+            let total_weight = total_weight as u128;
+            (
+                vk,
+                (numerator / total_weight) as u64,
+                (numerator % total_weight) as u64,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut leftover =
+        voting_power - allocations.iter().map(|(_, quota, _)| quota).sum::<u64>();
+
+    allocations.sort_by(|(vk_a, _, remainder_a), (vk_b, _, remainder_b)| {
+        remainder_b.cmp(remainder_a).then_with(|| vk_a.cmp(vk_b))
+    });
+
+    for (_, quota, _) in allocations.iter_mut() {
+        if leftover == 0 {
+            break;
+        }
+        *quota += 1;
+        leftover -= 1;
+    }
+
+    allocations
+        .into_iter()
+        .map(|(vk, quota, _)| (vk, quota))
+        .collect()
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Snapshot {
     // a raw public key is preferred so that we don't have to worry about discrimination when deserializing from
     // a CIP-36 compatible encoding
     inner: BTreeMap<Identifier, Vec<KeyContribution>>,
     stake_threshold: Value,
+    voting_purpose: u64,
 }
 
 impl Snapshot {
-    pub fn from_raw_snapshot(raw_snapshot: RawSnapshot, stake_threshold: Value) -> Self {
-        Self {
-            inner: raw_snapshot
-                .0
-                .into_iter()
-                .filter(|reg| reg.voting_power >= stake_threshold)
-                // TODO: add capability to select voting purpose for a snapshot.
-                // At the moment Catalyst is the only one in use
-                .filter(|reg| reg.voting_purpose == CATALYST_VOTING_PURPOSE_TAG)
-                .fold(BTreeMap::new(), |mut acc, reg| {
-                    let VotingRegistration {
+    /// Builds a snapshot per voting purpose found in `raw_snapshot` that matches
+    /// `voting_purpose`, keyed by the purpose tag. Requesting a single
+    /// [`VotingPurpose::Tag`] still returns a map, but with at most one entry, so
+    /// that callers don't need two different APIs depending on the selection.
+    pub fn from_raw_snapshot(
+        raw_snapshot: RawSnapshot,
+        voting_purpose: VotingPurpose,
+        stake_threshold: Value,
+    ) -> BTreeMap<u64, Self> {
+        let mut by_purpose: BTreeMap<u64, BTreeMap<Identifier, Vec<KeyContribution>>> =
+            BTreeMap::new();
+
+        for reg in keep_latest_registration_per_stake_key(raw_snapshot.0)
+            .into_iter()
+            .filter(|reg| reg.voting_power >= stake_threshold)
+            .filter(|reg| voting_purpose.accepts(reg.voting_purpose))
+        {
+            let VotingRegistration {
+                reward_address,
+                delegations,
+                voting_power,
+                voting_purpose,
+                ..
+            } = reg;
+            let acc = by_purpose.entry(voting_purpose).or_default();
+
+            match delegations {
+                Delegations::Legacy(vk) => {
+                    acc.entry(vk).or_default().push(KeyContribution {
                         reward_address,
-                        delegations,
-                        voting_power,
-                        ..
-                    } = reg;
-
-                    match delegations {
-                        Delegations::Legacy(vk) => {
-                            acc.entry(vk).or_default().push(KeyContribution {
-                                reward_address,
-                                value: voting_power.into(),
-                            });
-                        }
-                        Delegations::New(mut vks) => {
-                            let voting_power = u64::from(voting_power);
-                            let total_weights =
-                                vks.iter().map(|(_vk, weight)| *weight as u64).sum::<u64>();
-
-                            let last = vks.pop().expect("CIP36 requires at least 1 delegation");
-                            let others_total_vp = vks
-                                .into_iter()
-                                .filter_map(|(vk, weight)| {
-                                    NonZeroU64::new((voting_power * weight as u64) / total_weights)
-                                        .map(|value| (vk, value))
-                                })
-                                .map(|(vk, value)| {
-                                    acc.entry(vk).or_default().push(KeyContribution {
-                                        reward_address: reward_address.clone(),
-                                        value: value.get(),
-                                    });
-                                    value.get()
-                                })
-                                .sum::<u64>();
-                            if others_total_vp != voting_power {
-                                acc.entry(last.0).or_default().push(KeyContribution {
-                                    reward_address,
-                                    value: voting_power - others_total_vp,
-                                });
-                            }
-                        }
-                    };
-                    acc
-                }),
-            stake_threshold,
+                        value: voting_power.into(),
+                    });
+                }
+                Delegations::New(vks) => {
+                    let voting_power = u64::from(voting_power);
+                    let total_weights =
+                        vks.iter().map(|(_vk, weight)| *weight as u64).sum::<u64>();
+
+                    for (vk, value) in largest_remainder_apportionment(voting_power, vks, total_weights)
+                    {
+                        acc.entry(vk).or_default().push(KeyContribution {
+                            reward_address: reward_address.clone(),
+                            value,
+                        });
+                    }
+                }
+            };
         }
+
+        by_purpose
+            .into_iter()
+            .map(|(purpose, inner)| {
+                (
+                    purpose,
+                    Self {
+                        inner,
+                        stake_threshold,
+                        voting_purpose: purpose,
+                    },
+                )
+            })
+            .collect()
     }
 
     pub fn stake_threshold(&self) -> Value {
@@ -120,6 +275,23 @@ impl Snapshot {
             .cloned()
             .unwrap_or_default()
     }
+
+    /// Full export of the snapshot, keeping the contribution provenance and
+    /// stake-threshold metadata that [`Snapshot::to_block0_initials`] collapses
+    /// away. Meant for analysts and treasury tooling rather than block0.
+    pub fn to_full_snapshot_info(&self) -> Vec<SnapshotEntry> {
+        let stake_threshold = u64::from(self.stake_threshold);
+        self.inner
+            .iter()
+            .map(|(vk, contributions)| SnapshotEntry {
+                voting_key: vk.to_bech32_str(),
+                voting_power: contributions.iter().map(|c| c.value).sum(),
+                voting_purpose: self.voting_purpose,
+                stake_threshold,
+                contributions: contributions.clone(),
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -149,24 +321,39 @@ mod tests {
             .cloned()
             .collect::<Vec<_>>();
         assert_eq!(
-            Snapshot::from_raw_snapshot(raw.clone(), stake_threshold.into()),
+            Snapshot::from_raw_snapshot(
+                raw.clone(),
+                VotingPurpose::default(),
+                stake_threshold.into()
+            ),
             Snapshot::from_raw_snapshot(
                 RawSnapshot(filtered_snapshot.clone()),
+                VotingPurpose::default(),
                 stake_threshold.into()
             )
         );
-        let mut snapshot = Snapshot::from_raw_snapshot(RawSnapshot(filtered_snapshot), 0.into());
-        snapshot.stake_threshold = stake_threshold.into();
+        let mut snapshot = Snapshot::from_raw_snapshot(
+            RawSnapshot(filtered_snapshot),
+            VotingPurpose::default(),
+            0.into(),
+        );
+        for snapshot in snapshot.values_mut() {
+            snapshot.stake_threshold = stake_threshold.into();
+        }
         assert_eq!(
-            Snapshot::from_raw_snapshot(raw, stake_threshold.into()),
+            Snapshot::from_raw_snapshot(raw, VotingPurpose::default(), stake_threshold.into()),
             snapshot
         );
     }
 
     // Test all voting power is distributed among delegated keys
     #[quickcheck]
-    fn test_voting_power_all_distributed(reg: VotingRegistration) {
-        let snapshot = Snapshot::from_raw_snapshot(vec![reg.clone()].into(), 0.into());
+    fn test_voting_power_all_distributed(mut reg: VotingRegistration) {
+        reg.voting_purpose = 0;
+        let voting_power = reg.voting_power;
+        let snapshot = Snapshot::from_raw_snapshot(vec![reg].into(), VotingPurpose::Tag(0), 0.into())
+            .remove(&0)
+            .unwrap();
         let total_stake =
             if let Initial::Fund(utxos) = snapshot.to_block0_initials(Discrimination::Test) {
                 utxos
@@ -176,18 +363,39 @@ mod tests {
             } else {
                 unreachable!()
             };
-        assert_eq!(total_stake, u64::from(reg.voting_power))
+        assert_eq!(total_stake, u64::from(voting_power))
     }
 
     #[quickcheck]
     fn test_non_catalyst_regs_are_ignored(mut reg: VotingRegistration) {
         reg.voting_purpose = 1;
         assert_eq!(
-            Snapshot::from_raw_snapshot(vec![reg].into(), 0.into()),
-            Snapshot::from_raw_snapshot(vec![].into(), 0.into()),
+            Snapshot::from_raw_snapshot(vec![reg].into(), VotingPurpose::Tag(0), 0.into()),
+            Snapshot::from_raw_snapshot(vec![].into(), VotingPurpose::Tag(0), 0.into()),
         )
     }
 
+    #[test]
+    fn test_all_voting_purposes_are_grouped() {
+        let mut reg_purpose_0 = VotingRegistration::arbitrary(&mut quickcheck::StdThreadGen::new(10));
+        reg_purpose_0.voting_purpose = 0;
+        // Distinct stake key: otherwise keep_latest_registration_per_stake_key
+        // would see two same-nonce registrations for the same key and drop
+        // this one before the purpose split under test ever runs.
+        let mut reg_purpose_1 = reg_purpose_0.clone();
+        reg_purpose_1.stake_public_key = format!("{}_other", reg_purpose_1.stake_public_key);
+        reg_purpose_1.voting_purpose = 1;
+
+        let snapshots = Snapshot::from_raw_snapshot(
+            vec![reg_purpose_0, reg_purpose_1].into(),
+            VotingPurpose::All,
+            0.into(),
+        );
+        assert_eq!(snapshots.len(), 2);
+        assert!(snapshots.contains_key(&0));
+        assert!(snapshots.contains_key(&1));
+    }
+
     #[test]
     fn test_distribution() {
         let mut raw_snapshot = Vec::new();
@@ -200,15 +408,18 @@ mod tests {
                 (voting_pub_key_2.clone(), 1),
             ]);
             raw_snapshot.push(VotingRegistration {
-                stake_public_key: String::new(),
+                stake_public_key: i.to_string(),
                 voting_power: i.into(),
                 reward_address: String::new(),
                 delegations,
                 voting_purpose: 0,
+                nonce: 0,
             });
         }
 
-        let snapshot = Snapshot::from_raw_snapshot(raw_snapshot.into(), 0.into());
+        let snapshot = Snapshot::from_raw_snapshot(raw_snapshot.into(), VotingPurpose::Tag(0), 0.into())
+            .remove(&0)
+            .unwrap();
         let vp_1: u64 = snapshot
             .contributions_for_voting_key(voting_pub_key_1)
             .into_iter()
@@ -219,16 +430,200 @@ mod tests {
             .into_iter()
             .map(|c| c.value)
             .sum();
-        assert_eq!(vp_2, 30); // last key get the remainder during distributiong
-        assert_eq!(vp_1, 25);
+        // Both keys have equal weight, so they get equal quotas; the leftover unit
+        // from an odd voting power goes to vk_1, whose byte representation is
+        // smaller, making the allocation independent of delegation order.
+        assert_eq!(vp_1, 30);
+        assert_eq!(vp_2, 25);
+    }
+
+    #[test]
+    fn test_distribution_is_order_independent() {
+        let voting_pub_key_1 = Identifier::from_hex(&hex::encode([0; 32])).unwrap();
+        let voting_pub_key_2 = Identifier::from_hex(&hex::encode([1; 32])).unwrap();
+
+        let reg = |delegations| VotingRegistration {
+            stake_public_key: String::new(),
+            voting_power: 7.into(),
+            reward_address: String::new(),
+            delegations,
+            voting_purpose: 0,
+            nonce: 0,
+        };
+
+        let forward = Snapshot::from_raw_snapshot(
+            vec![reg(Delegations::New(vec![
+                (voting_pub_key_1.clone(), 1),
+                (voting_pub_key_2.clone(), 1),
+            ]))]
+            .into(),
+            VotingPurpose::Tag(0),
+            0.into(),
+        );
+        let reversed = Snapshot::from_raw_snapshot(
+            vec![reg(Delegations::New(vec![
+                (voting_pub_key_2, 1),
+                (voting_pub_key_1, 1),
+            ]))]
+            .into(),
+            VotingPurpose::Tag(0),
+            0.into(),
+        );
+
+        assert_eq!(forward, reversed);
+    }
+
+    // A delegation whose weight is too small to earn any unit still shows up
+    // in the snapshot with a zero contribution, instead of disappearing.
+    #[test]
+    fn test_zero_quota_delegation_is_not_dropped() {
+        let voting_pub_key_1 = Identifier::from_hex(&hex::encode([0; 32])).unwrap();
+        let voting_pub_key_2 = Identifier::from_hex(&hex::encode([1; 32])).unwrap();
+
+        let raw_snapshot = vec![VotingRegistration {
+            stake_public_key: String::new(),
+            voting_power: 1.into(),
+            reward_address: String::new(),
+            delegations: Delegations::New(vec![
+                (voting_pub_key_1.clone(), 1),
+                (voting_pub_key_2.clone(), 1000),
+            ]),
+            voting_purpose: 0,
+            nonce: 0,
+        }];
+
+        let snapshot = Snapshot::from_raw_snapshot(raw_snapshot.into(), VotingPurpose::Tag(0), 0.into())
+            .remove(&0)
+            .unwrap();
+        assert!(snapshot.voting_keys().any(|vk| *vk == voting_pub_key_1));
+        assert_eq!(
+            snapshot
+                .contributions_for_voting_key(voting_pub_key_1)
+                .into_iter()
+                .map(|c| c.value)
+                .sum::<u64>(),
+            0
+        );
+    }
+
+    // A registration where every delegated key has weight 0 must not panic
+    // (dividing by a total weight of 0) and should leave every key with a
+    // zero contribution instead of dropping them.
+    #[test]
+    fn test_all_zero_weight_delegations_does_not_panic() {
+        let voting_pub_key_1 = Identifier::from_hex(&hex::encode([0; 32])).unwrap();
+        let voting_pub_key_2 = Identifier::from_hex(&hex::encode([1; 32])).unwrap();
+
+        let raw_snapshot = vec![VotingRegistration {
+            stake_public_key: String::new(),
+            voting_power: 10.into(),
+            reward_address: String::new(),
+            delegations: Delegations::New(vec![
+                (voting_pub_key_1.clone(), 0),
+                (voting_pub_key_2.clone(), 0),
+            ]),
+            voting_purpose: 0,
+            nonce: 0,
+        }];
+
+        let snapshot = Snapshot::from_raw_snapshot(raw_snapshot.into(), VotingPurpose::Tag(0), 0.into())
+            .remove(&0)
+            .unwrap();
+
+        for vk in [voting_pub_key_1, voting_pub_key_2] {
+            assert!(snapshot.voting_keys().any(|k| *k == vk));
+            assert_eq!(
+                snapshot
+                    .contributions_for_voting_key(vk)
+                    .into_iter()
+                    .map(|c| c.value)
+                    .sum::<u64>(),
+                0
+            );
+        }
+    }
+
+    #[test]
+    fn test_re_registration_keeps_only_highest_nonce() {
+        let voting_pub_key_1 = Identifier::from_hex(&hex::encode([0; 32])).unwrap();
+        let voting_pub_key_2 = Identifier::from_hex(&hex::encode([1; 32])).unwrap();
+
+        let reg = |voting_power: u64, delegations, nonce| VotingRegistration {
+            stake_public_key: "same_stake_key".to_string(),
+            voting_power: voting_power.into(),
+            reward_address: String::new(),
+            delegations,
+            voting_purpose: 0,
+            nonce,
+        };
+
+        let raw_snapshot = vec![
+            reg(10, Delegations::Legacy(voting_pub_key_1.clone()), 0),
+            reg(20, Delegations::Legacy(voting_pub_key_2.clone()), 1),
+        ];
+
+        let snapshot = Snapshot::from_raw_snapshot(raw_snapshot.into(), VotingPurpose::Tag(0), 0.into())
+            .remove(&0)
+            .unwrap();
+
+        assert_eq!(
+            snapshot
+                .contributions_for_voting_key(voting_pub_key_2)
+                .into_iter()
+                .map(|c| c.value)
+                .sum::<u64>(),
+            20
+        );
+        assert!(snapshot
+            .contributions_for_voting_key(voting_pub_key_1)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_to_full_snapshot_info() {
+        let voting_pub_key_1 = Identifier::from_hex(&hex::encode([0; 32])).unwrap();
+
+        let raw_snapshot = vec![VotingRegistration {
+            stake_public_key: String::new(),
+            voting_power: 10.into(),
+            reward_address: "reward_address".to_string(),
+            delegations: Delegations::Legacy(voting_pub_key_1.clone()),
+            voting_purpose: 0,
+            nonce: 0,
+        }];
+
+        let snapshot = Snapshot::from_raw_snapshot(raw_snapshot.into(), VotingPurpose::Tag(0), 7.into())
+            .remove(&0)
+            .unwrap();
+
+        let entries = snapshot.to_full_snapshot_info();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].voting_key, voting_pub_key_1.to_bech32_str());
+        assert_eq!(entries[0].voting_power, 10);
+        assert_eq!(entries[0].voting_purpose, 0);
+        assert_eq!(entries[0].stake_threshold, 7);
+        assert_eq!(
+            entries[0].contributions,
+            vec![KeyContribution {
+                reward_address: "reward_address".to_string(),
+                value: 10,
+            }]
+        );
     }
 
     impl Arbitrary for Snapshot {
         fn arbitrary<G: Gen>(g: &mut G) -> Self {
             Self::from_raw_snapshot(
                 <_>::arbitrary(g),
+                VotingPurpose::default(),
                 (u64::from(NonZeroU64::arbitrary(g))).into(),
             )
+            .remove(&CATALYST_VOTING_PURPOSE_TAG)
+            .unwrap_or_else(|| Self {
+                inner: BTreeMap::new(),
+                stake_threshold: 0.into(),
+                voting_purpose: CATALYST_VOTING_PURPOSE_TAG,
+            })
         }
     }
 
@@ -253,6 +648,6 @@ mod tests {
                 }
         ]"#,
         ).unwrap();
-        Snapshot::from_raw_snapshot(raw, 0.into());
+        Snapshot::from_raw_snapshot(raw, VotingPurpose::default(), 0.into());
     }
 }
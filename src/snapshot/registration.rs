@@ -0,0 +1,121 @@
+use jormungandr_lib::crypto::account::Identifier;
+use jormungandr_lib::interfaces::Value;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+
+/// Bech32-encoded mainnet Shelley address collecting the registration's rewards.
+pub type MainnetRewardAddress = String;
+
+/// A CIP-36 delegation: either the legacy format where a stake key delegates
+/// its full voting power to a single voting key, or the newer format where it
+/// is split across several voting keys according to a weight.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Delegations {
+    Legacy(Identifier),
+    New(Vec<(Identifier, u32)>),
+}
+
+/// A single CIP-36 voting registration, as recovered from the chain metadata.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VotingRegistration {
+    pub stake_public_key: String,
+    pub voting_power: Value,
+    pub reward_address: MainnetRewardAddress,
+    pub delegations: Delegations,
+    pub voting_purpose: u64,
+    /// Registrations for the same `stake_public_key` are deduplicated by
+    /// keeping only the one with the highest nonce, matching CIP-36
+    /// re-registration semantics.
+    pub nonce: u64,
+}
+
+#[derive(Deserialize)]
+struct RawVotingRegistration {
+    reward_address: String,
+    stake_public_key: String,
+    #[serde(rename = "total_voting_power")]
+    voting_power: u64,
+    delegations: RawDelegations,
+    #[serde(default)]
+    voting_purpose: u64,
+    #[serde(default)]
+    nonce: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawDelegations {
+    Legacy(String),
+    New(BTreeMap<String, u32>),
+}
+
+impl<'de> Deserialize<'de> for VotingRegistration {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = RawVotingRegistration::deserialize(deserializer)?;
+        let delegations = match raw.delegations {
+            RawDelegations::Legacy(vk) => Delegations::Legacy(
+                Identifier::from_hex(vk.trim_start_matches("0x")).map_err(D::Error::custom)?,
+            ),
+            RawDelegations::New(vks) => Delegations::New(
+                vks.into_iter()
+                    .map(|(vk, weight)| {
+                        Identifier::from_hex(vk.trim_start_matches("0x"))
+                            .map(|vk| (vk, weight))
+                            .map_err(D::Error::custom)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+        };
+
+        Ok(VotingRegistration {
+            stake_public_key: raw.stake_public_key,
+            voting_power: raw.voting_power.into(),
+            reward_address: raw.reward_address,
+            delegations,
+            voting_purpose: raw.voting_purpose,
+            nonce: raw.nonce,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::{Arbitrary, Gen};
+
+    impl Arbitrary for Delegations {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            let identifier = |g: &mut G| {
+                let bytes: Vec<u8> = (0..32).map(|_| u8::arbitrary(g)).collect();
+                Identifier::from_hex(&hex::encode(bytes)).unwrap()
+            };
+            if bool::arbitrary(g) {
+                Delegations::Legacy(identifier(g))
+            } else {
+                let n = (usize::arbitrary(g) % 8) + 1;
+                Delegations::New((0..n).map(|_| (identifier(g), u32::arbitrary(g))).collect())
+            }
+        }
+    }
+
+    impl Arbitrary for VotingRegistration {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            // Distinct per instance: callers like `test_threshold` build several
+            // registrations and rely on them not colliding on the stake key now
+            // that registrations are deduplicated by stake key before filtering.
+            let stake_public_key: Vec<u8> = (0..32).map(|_| u8::arbitrary(g)).collect();
+            VotingRegistration {
+                stake_public_key: hex::encode(stake_public_key),
+                voting_power: u64::arbitrary(g).into(),
+                reward_address: String::new(),
+                delegations: Delegations::arbitrary(g),
+                voting_purpose: 0,
+                nonce: u64::arbitrary(g),
+            }
+        }
+    }
+}